@@ -0,0 +1,32 @@
+// -*- mode: rust; -*-
+//
+// To the extent possible under law, the authors have waived all copyright and
+// related or neighboring rights to curve25519-dalek, using the Creative
+// Commons "CC0" public domain dedication.  See
+// <http://creativecommons.org/publicdomain/zero/.0/> for full details.
+
+//! Small constant-time helpers shared across the crate's field and
+//! point-compression implementations, so the same bit trick isn't
+//! re-derived (and potentially re-broken) at each call site.
+
+/// `1u8` if `x == 0`, `0u8` otherwise, computed without branching on `x`.
+///
+/// For `x: u8` widened to `i32`, `x | -x` has its sign bit set iff
+/// `x != 0` (since `-0 == 0`, and for `x != 0` either `x` or `-x` is
+/// negative); shifting that sign bit down and adding `1` turns "sign bit
+/// set" into `0` and "sign bit clear" into `1`.
+pub fn is_zero_mask(x: u8) -> u8 {
+    let d = x as i32;
+    (((d | -d) >> 31) + 1) as u8
+}
+
+/// Check two equal-length byte slices for equality in constant time,
+/// returning `1u8` if they are equal and `0u8` otherwise.
+pub fn bytes_equal_ct(a: &[u8], b: &[u8]) -> u8 {
+    debug_assert_eq!(a.len(), b.len());
+    let mut diff = 0u8;
+    for i in 0..a.len() {
+        diff |= a[i] ^ b[i];
+    }
+    is_zero_mask(diff)
+}
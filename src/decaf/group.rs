@@ -0,0 +1,206 @@
+// -*- mode: rust; -*-
+//
+// To the extent possible under law, the authors have waived all copyright and
+// related or neighboring rights to curve25519-dalek, using the Creative
+// Commons "CC0" public domain dedication.  See
+// <http://creativecommons.org/publicdomain/zero/.0/> for full details.
+
+//! An abstract prime-order group, implemented here by the Decaf group.
+//!
+//! Downstream protocol code (signatures, commitments, VRFs, ...) often
+//! wants to be generic over "a prime-order group with a compressed
+//! encoding" rather than hard-coding `ExtendedPoint` / `CompressedDecaf`
+//! directly.  This module factors that interface out into a small trait
+//! family and implements it for the Decaf group defined in the parent
+//! module.
+//!
+//! Decaf deliberately has no distinct "uncompressed" point format: the
+//! `Compressed` trait is the only serialization a `PrimeOrderGroup`
+//! exposes, so generic callers never need to know they are really
+//! working with a twisted Edwards curve underneath. Every encoding
+//! produced by a given `Compressed` implementation is exactly
+//! `Compressed::LENGTH` bytes, even though `to_bytes` returns a `Vec<u8>`
+//! rather than a fixed-size array.
+
+use curve::ExtendedPoint;
+use curve::Identity;
+use scalar::Scalar;
+use constants;
+
+use super::CompressedDecaf;
+
+/// The canonical, fixed-length compressed encoding of a group element.
+pub trait Compressed: Copy + Clone + Eq + PartialEq + Sized {
+    /// The group element this compressed form decodes to.
+    type Element;
+
+    /// The length in bytes of this encoding. `to_bytes` always returns
+    /// exactly this many bytes, and `from_bytes` rejects any input whose
+    /// length differs.
+    const LENGTH: usize;
+
+    /// Serialize this encoding to bytes.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Deserialize an encoding from bytes, without attempting to
+    /// decompress it to a group element.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Attempt to decompress to a group element, validating the
+    /// encoding in the process.
+    fn decompress(&self) -> Option<Self::Element>;
+}
+
+/// A single element of a `PrimeOrderGroup`.
+pub trait GroupElement: Copy + Clone + Sized {
+    /// The group this element belongs to.
+    type Group: PrimeOrderGroup<Element = Self>;
+
+    /// Add two group elements.
+    fn add(&self, other: &Self) -> Self;
+
+    /// Negate a group element.
+    fn negate(&self) -> Self;
+
+    /// Multiply this element by a scalar.
+    fn scalar_mult(&self, scalar: &<Self::Group as PrimeOrderGroup>::Scalar) -> Self;
+
+    /// Compress this element to its canonical encoding.
+    fn compress(&self) -> <Self::Group as PrimeOrderGroup>::Compressed;
+}
+
+/// A cryptographic group of prime order, with a compressed encoding.
+pub trait PrimeOrderGroup {
+    /// An element of the group.
+    type Element: GroupElement<Group = Self>;
+    /// The scalars that act on `Element`s via `GroupElement::scalar_mult`.
+    type Scalar;
+    /// The canonical compressed encoding of an `Element`.
+    type Compressed: Compressed<Element = Self::Element>;
+
+    /// The identity element.
+    fn identity() -> Self::Element;
+
+    /// The distinguished generator (basepoint) of the group.
+    fn generator() -> Self::Element;
+}
+
+// ------------------------------------------------------------------------
+// The Decaf group
+// ------------------------------------------------------------------------
+
+/// The Decaf prime-order group built on Curve25519.
+pub struct DecafGroup;
+
+/// An element of the `DecafGroup`, wrapping the underlying `ExtendedPoint`.
+#[derive(Copy, Clone)]
+pub struct DecafElement(pub ExtendedPoint);
+
+impl PrimeOrderGroup for DecafGroup {
+    type Element = DecafElement;
+    type Scalar = Scalar;
+    type Compressed = CompressedDecaf;
+
+    fn identity() -> DecafElement {
+        DecafElement(ExtendedPoint::identity())
+    }
+
+    fn generator() -> DecafElement {
+        DecafElement(constants::BASE_CMPRSSD.decompress().unwrap())
+    }
+}
+
+impl GroupElement for DecafElement {
+    type Group = DecafGroup;
+
+    fn add(&self, other: &DecafElement) -> DecafElement {
+        DecafElement(&self.0 + &other.0)
+    }
+
+    fn negate(&self) -> DecafElement {
+        DecafElement(-&self.0)
+    }
+
+    fn scalar_mult(&self, scalar: &Scalar) -> DecafElement {
+        DecafElement(self.0.scalar_mult(scalar))
+    }
+
+    fn compress(&self) -> CompressedDecaf {
+        self.0.compress_decaf()
+    }
+}
+
+impl Compressed for CompressedDecaf {
+    type Element = DecafElement;
+
+    const LENGTH: usize = 32;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        CompressedDecaf::to_bytes(self).to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<CompressedDecaf> {
+        if bytes.len() != <CompressedDecaf as Compressed>::LENGTH {
+            return None;
+        }
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(bytes);
+        Some(CompressedDecaf(buf))
+    }
+
+    fn decompress(&self) -> Option<DecafElement> {
+        CompressedDecaf::decompress(self).map(DecafElement)
+    }
+}
+
+// ------------------------------------------------------------------------
+// Tests
+// ------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use rand::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn test_decaf_group_identity_and_generator_distinct() {
+        let id = DecafGroup::identity();
+        let gen = DecafGroup::generator();
+        assert!(id.compress() != gen.compress());
+    }
+
+    #[test]
+    fn test_decaf_group_add_identity_is_noop() {
+        let gen = DecafGroup::generator();
+        let id = DecafGroup::identity();
+        assert_eq!(gen.add(&id).compress(), gen.compress());
+    }
+
+    #[test]
+    fn test_decaf_group_scalar_mult_matches_basepoint_mult() {
+        let mut rng = OsRng::new().unwrap();
+        let s = Scalar::random(&mut rng);
+
+        let via_group = DecafGroup::generator().scalar_mult(&s);
+        let via_point = ExtendedPoint::basepoint_mult(&s);
+        assert_eq!(via_group.compress(), via_point.compress_decaf());
+    }
+
+    #[test]
+    fn test_decaf_group_compress_decompress_roundtrip() {
+        let mut rng = OsRng::new().unwrap();
+        let s = Scalar::random(&mut rng);
+        let element = DecafGroup::generator().scalar_mult(&s);
+
+        let compressed = element.compress();
+        let decompressed = <CompressedDecaf as Compressed>::decompress(&compressed).unwrap();
+        assert_eq!(decompressed.compress(), compressed);
+    }
+
+    #[test]
+    fn test_compressed_decaf_from_bytes_checks_length() {
+        assert!(CompressedDecaf::from_bytes(&[0u8; 31]).is_none());
+        assert!(CompressedDecaf::from_bytes(&[0u8; 32]).is_some());
+    }
+}
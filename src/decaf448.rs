@@ -0,0 +1,251 @@
+// -*- mode: rust; -*-
+//
+// To the extent possible under law, the authors have waived all copyright and
+// related or neighboring rights to curve25519-dalek, using the Creative
+// Commons "CC0" public domain dedication.  See
+// <http://creativecommons.org/publicdomain/zero/.0/> for full details.
+
+//! Mike Hamburg's Decaf point-compression scheme over the Ed448-Goldilocks
+//! curve ("Decaf448"), providing a 224-bit-security, cofactor-4
+//! prime-order group alongside the Curve25519 `decaf` module.
+//!
+//! This shares its decode/encode algorithm with `decaf` via
+//! `decaf_generic`: both curves' `decompress`/`compress_decaf` delegate
+//! to the same `decaf_generic::decode`/`decode_finish`/
+//! `encode_with_z_inv` functions, parameterized here by `Decaf448Params`
+//! (`a = 1`, cofactor 4) and a `DecafField` impl for `FieldElement448`
+//! below. A cofactor-4 curve needs no pre-rotation over `E[8]` the way
+//! the Curve25519 instantiation does -- `decaf_generic` branches on
+//! `DecafParams::COFACTOR_EIGHT` to pick the simpler two-torsion sign
+//! adjustment instead.
+//!
+//! XXX this module only has the decode/encode paths: there is no
+//! `field448`-based scalar or basepoint-multiplication implementation
+//! yet, so (unlike `decaf`'s tests) the tests below can't exercise a
+//! real basepoint or the full four-torsion group.
+//!
+//! XXX `field448::FieldElement448`'s arithmetic is a straightforward
+//! schoolbook implementation and has *not* been hardened to run in
+//! constant time the same way `field::FieldElement` has (see
+//! `field448`'s module docs; for instance, `reduce_fully`'s final
+//! conditional subtraction branches on a computed value). Don't feed
+//! secret-derived coordinates through `ExtendedPoint448::compress_decaf`
+//! or `CompressedDecaf448::decompress` without addressing that first.
+
+#![allow(non_snake_case)]
+
+use core::fmt::Debug;
+
+use subtle::CTAssignable;
+
+use field448::FieldElement448;
+
+use decaf_generic;
+use decaf_generic::{DecafField, DecafParams};
+
+mod constants448 {
+    use field448::FieldElement448;
+
+    /// `d` for the Ed448-Goldilocks curve `x^2 + y^2 = 1 + d x^2 y^2`.
+    pub fn d() -> FieldElement448 {
+        let mut d = FieldElement448::zero();
+        d[0] = 39081;
+        d.negate();
+        d
+    }
+
+    /// `4*d`.
+    pub fn d4() -> FieldElement448 {
+        let d = d();
+        &(&d + &d) + &(&d + &d)
+    }
+
+    /// `a - d = 1 - d`, for `a = 1`.
+    pub fn a_minus_d() -> FieldElement448 {
+        &FieldElement448::one() - &d()
+    }
+}
+
+/// This curve's decode/encode parameters for `decaf_generic`: Ed448 is
+/// the cofactor-4 case, so encoding only needs the two-torsion negation,
+/// not the `E[8]` pre-rotation (`sqrt_m1` is accordingly unreachable).
+struct Decaf448Params;
+
+impl DecafParams for Decaf448Params {
+    type Field = FieldElement448;
+
+    const COFACTOR_EIGHT: bool = false;
+
+    fn d() -> FieldElement448 { constants448::d() }
+    fn d4() -> FieldElement448 { constants448::d4() }
+    fn a_minus_d() -> FieldElement448 { constants448::a_minus_d() }
+    fn sqrt_m1() -> FieldElement448 { unreachable!("Ed448 is cofactor 4: no E[8] pre-rotation") }
+}
+
+impl DecafField for FieldElement448 {
+    fn zero() -> FieldElement448 { FieldElement448::zero() }
+    fn one() -> FieldElement448 { FieldElement448::one() }
+    fn add(&self, other: &FieldElement448) -> FieldElement448 { self + other }
+    fn sub(&self, other: &FieldElement448) -> FieldElement448 { self - other }
+    fn mul(&self, other: &FieldElement448) -> FieldElement448 { self * other }
+    fn neg(&self) -> FieldElement448 { -self }
+    fn square(&self) -> FieldElement448 { FieldElement448::square(self) }
+    fn invert(&self) -> FieldElement448 { FieldElement448::invert(self) }
+    fn invsqrt(&self) -> Option<FieldElement448> { FieldElement448::invsqrt(self) }
+    fn is_zero(&self) -> u8 { FieldElement448::is_zero(self) }
+    fn is_nonzero(&self) -> u8 { FieldElement448::is_nonzero(self) }
+    fn is_negative_decaf(&self) -> u8 { FieldElement448::is_negative_decaf(self) }
+    fn is_nonnegative_decaf(&self) -> u8 { FieldElement448::is_nonnegative_decaf(self) }
+    fn negate(&mut self) { FieldElement448::negate(self) }
+    fn abs_decaf(&self) -> FieldElement448 { FieldElement448::abs_decaf(self) }
+    fn ct_assign(&mut self, other: &FieldElement448, choice: u8) {
+        <FieldElement448 as CTAssignable>::conditional_assign(self, other, choice)
+    }
+}
+
+// ------------------------------------------------------------------------
+// Points
+// ------------------------------------------------------------------------
+
+/// A point on the Ed448-Goldilocks curve, in extended coordinates.
+#[derive(Copy, Clone)]
+pub struct ExtendedPoint448 {
+    pub X: FieldElement448,
+    pub Y: FieldElement448,
+    pub Z: FieldElement448,
+    pub T: FieldElement448,
+}
+
+impl ExtendedPoint448 {
+    /// The identity point `(0, 1)`.
+    pub fn identity() -> ExtendedPoint448 {
+        ExtendedPoint448 {
+            X: FieldElement448::zero(),
+            Y: FieldElement448::one(),
+            Z: FieldElement448::one(),
+            T: FieldElement448::zero(),
+        }
+    }
+
+    /// Compress in Decaf448 format.
+    ///
+    /// # Warning
+    ///
+    /// Uses `field448::FieldElement448`, which is not constant-time
+    /// hardened (see this module's doc comment). Don't call this on a
+    /// point with secret-derived coordinates without addressing that
+    /// first.
+    pub fn compress_decaf(&self) -> CompressedDecaf448 {
+        // XXX it should be possible to avoid this inversion, as in `decaf`.
+        let z_inv = self.Z.invert();
+        let s = decaf_generic::encode_with_z_inv::<Decaf448Params>(self.X, self.Y, self.Z, self.T, &z_inv);
+        CompressedDecaf448(s.to_bytes())
+    }
+}
+
+// ------------------------------------------------------------------------
+// Compressed points
+// ------------------------------------------------------------------------
+
+/// A point serialized using Mike Hamburg's Decaf scheme, over Ed448.
+#[derive(Copy, Clone)]
+pub struct CompressedDecaf448(pub [u8; 56]);
+
+impl PartialEq for CompressedDecaf448 {
+    fn eq(&self, other: &CompressedDecaf448) -> bool {
+        &self.0[..] == &other.0[..]
+    }
+}
+
+impl Eq for CompressedDecaf448 {}
+
+impl CompressedDecaf448 {
+    /// View this `CompressedDecaf448` as an array of bytes.
+    pub fn to_bytes(&self) -> [u8; 56] {
+        self.0
+    }
+
+    /// Attempt to decompress to an `ExtendedPoint448`.
+    ///
+    /// Shares its decode algorithm with `decaf::CompressedDecaf::decompress`
+    /// via `decaf_generic`, parameterized for the Ed448 field and curve
+    /// constants (`a = 1`): rejects a non-canonical `s` encoding, a
+    /// negative `s`, and a recovered point whose `xy = T/Z` is negative,
+    /// with all checks folded into a single final decision the same way.
+    ///
+    /// # Warning
+    ///
+    /// Uses `field448::FieldElement448`, which is not constant-time
+    /// hardened (see this module's doc comment). Don't call this on
+    /// bytes that might encode secret-derived coordinates without
+    /// addressing that first.
+    pub fn decompress(&self) -> Option<ExtendedPoint448> {
+        let s = FieldElement448::from_bytes(&self.0);
+        let s_encoding_is_canonical = decaf_generic::bytes_equal_ct(&s.to_bytes(), &self.0);
+        let st = match decaf_generic::decode::<Decaf448Params>(s, s_encoding_is_canonical) {
+            Some(st) => st,
+            None => return None,
+        };
+        let z_inv = st.Z.invert();
+        decaf_generic::decode_finish(&st, &z_inv)
+            .map(|(X, Y, Z, T)| ExtendedPoint448 { X: X, Y: Y, Z: Z, T: T })
+    }
+}
+
+// ------------------------------------------------------------------------
+// Debug traits
+// ------------------------------------------------------------------------
+
+impl Debug for CompressedDecaf448 {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        write!(f, "CompressedDecaf448: {:?}", &self.0[..])
+    }
+}
+
+// ------------------------------------------------------------------------
+// Tests
+// ------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decaf448_compress_id() {
+        let id = ExtendedPoint448::identity();
+        assert_eq!(id.compress_decaf(), CompressedDecaf448([0u8; 56]));
+    }
+
+    #[test]
+    fn test_decaf448_decompress_id() {
+        let compressed_id = CompressedDecaf448([0u8; 56]);
+        let id = compressed_id.decompress().unwrap();
+        // Round-trip back through compression rather than comparing
+        // coordinates directly, since `ExtendedPoint448` has no
+        // canonical-coordinate equality of its own.
+        assert_eq!(id.compress_decaf(), compressed_id);
+    }
+
+    #[test]
+    fn test_decaf448_roundtrip_from_bytes() {
+        // Any canonical compressed encoding that decompresses
+        // successfully must re-compress to the same bytes; this doesn't
+        // require a verified basepoint or scalar multiplication, unlike
+        // `decaf`'s torsion tests.
+        // Not every small `s` is a canonical encoding of a point (e.g.
+        // `xy` may come out negative), so try a handful of candidates
+        // and make sure the roundtrip property is actually exercised at
+        // least once, rather than letting an all-`None` run report a
+        // silent pass.
+        let mut checked_at_least_one = false;
+        for s in 1u8..9 {
+            let mut bytes = [0u8; 56];
+            bytes[0] = s;
+            if let Some(p) = CompressedDecaf448(bytes).decompress() {
+                assert_eq!(p.compress_decaf(), CompressedDecaf448(bytes));
+                checked_at_least_one = true;
+            }
+        }
+        assert!(checked_at_least_one, "none of the candidate encodings decompressed");
+    }
+}
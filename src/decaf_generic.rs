@@ -0,0 +1,213 @@
+// -*- mode: rust; -*-
+//
+// To the extent possible under law, the authors have waived all copyright and
+// related or neighboring rights to curve25519-dalek, using the Creative
+// Commons "CC0" public domain dedication.  See
+// <http://creativecommons.org/publicdomain/zero/.0/> for full details.
+
+//! The generic core of Mike Hamburg's Decaf decode/encode algorithm,
+//! shared by `decaf` (Curve25519, cofactor 8) and `decaf448` (Ed448,
+//! cofactor 4).
+//!
+//! The two curves differ only in: which field they operate over, the
+//! curve's `d` / `4d` / `a-d` constants, and whether encoding needs the
+//! cofactor-8 pre-rotation over `E[8]` (Curve25519) or just the simpler
+//! cofactor-4 two-torsion negation (Ed448). Every other step -- the
+//! sequence of field operations that recovers `(X, Y, Z, T)` from `s`
+//! and back -- is identical between the two, and lives here exactly
+//! once, parameterized by the `DecafField` and `DecafParams` traits
+//! below. `decaf` and `decaf448` each provide a thin `DecafField` impl
+//! for their own field type (forwarding to that field's existing
+//! operators and methods) and a `DecafParams` impl for their own curve
+//! constants, then delegate their `decompress`/`compress_decaf` bodies
+//! to `decode`/`decode_finish`/`encode_with_z_inv` below.
+
+#![allow(non_snake_case)]
+
+pub use ct_util::{bytes_equal_ct, is_zero_mask};
+
+/// The field operations the generic Decaf decode/encode core needs.
+/// Implemented for `field::FieldElement` (in `decaf`) and
+/// `field448::FieldElement448` (in `decaf448`), each forwarding to that
+/// field's own inherent methods and operator overloads.
+pub trait DecafField: Sized + Copy + Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn sub(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+    fn neg(&self) -> Self;
+    fn square(&self) -> Self;
+    fn invert(&self) -> Self;
+    fn invsqrt(&self) -> Option<Self>;
+    fn is_zero(&self) -> u8;
+    fn is_nonzero(&self) -> u8;
+    fn is_negative_decaf(&self) -> u8;
+    fn is_nonnegative_decaf(&self) -> u8;
+    fn negate(&mut self);
+    fn abs_decaf(&self) -> Self;
+    /// Conditionally overwrite `self` with `other` in constant time,
+    /// named `ct_assign` rather than `conditional_assign` to avoid
+    /// colliding with `subtle::CTAssignable`'s method of that name,
+    /// which both concrete fields already implement.
+    fn ct_assign(&mut self, other: &Self, choice: u8);
+}
+
+/// The per-curve parameters the generic Decaf decode/encode core needs.
+pub trait DecafParams {
+    type Field: DecafField;
+
+    /// `true` for cofactor-8 curves (Curve25519, `a = -1`), which need
+    /// the `E[8]` pre-rotation during encoding; `false` for cofactor-4
+    /// curves (Ed448, `a = 1`), which only need the simpler two-torsion
+    /// negation.
+    const COFACTOR_EIGHT: bool;
+
+    /// The curve's `d`, from `a*x^2 + y^2 = 1 + d*x^2*y^2`.
+    fn d() -> Self::Field;
+    /// `4*d`.
+    fn d4() -> Self::Field;
+    /// `a - d`.
+    fn a_minus_d() -> Self::Field;
+    /// A square root of `-1` in the field. Only used when
+    /// `COFACTOR_EIGHT` is `true`; curves with `COFACTOR_EIGHT == false`
+    /// may implement this as `unreachable!()`.
+    fn sqrt_m1() -> Self::Field;
+}
+
+/// The state recovered from `s` by `decode`, before the final
+/// `Z`-inversion-dependent `xy >= 0` check: everything else a caller
+/// needs in order to finish validating (via `decode_finish`) once it
+/// has `1/Z`, however it chose to compute that inverse.
+pub struct DecodeState<F> {
+    pub s_encoding_is_canonical: u8,
+    pub s_is_negative: u8,
+    pub X: F,
+    pub Y: F,
+    pub Z: F,
+    pub T: F,
+}
+
+/// Decode `s` up to, but not including, the final `xy = T/Z` validity
+/// check (the one step that depends on an inversion of `Z`, and so is
+/// the one a batch caller wants to defer in order to amortize that
+/// inversion across many points via Montgomery's trick).
+///
+/// `s_encoding_is_canonical` must be computed by the caller (it depends
+/// on the curve's fixed byte length, which this generic core doesn't
+/// know about).
+///
+/// Returns `None` only on the (non-batchable) `invsqrt` failure.
+pub fn decode<P: DecafParams>(s: P::Field, s_encoding_is_canonical: u8) -> Option<DecodeState<P::Field>> {
+    let s_is_negative = s.is_negative_decaf();
+
+    let ss = s.square();
+    let X = s.add(&s); // X = 2s
+    let one = P::Field::one();
+    let Z = if P::COFACTOR_EIGHT { one.sub(&ss) } else { one.add(&ss) }; // Z = 1 + a*s^2
+    let u = Z.square().sub(&P::d4().mul(&ss)); // u = Z^2 - 4d*s^2
+    let uss = u.mul(&ss);
+    let mut v = match uss.invsqrt() {
+        Some(v) => v,
+        None => return None,
+    };
+    // Now v = 1/sqrt(u*s^2) if u*s^2 is a nonzero square, 0 if u*s^2 is zero.
+    let uv = v.mul(&u);
+    if uv.is_negative_decaf() == 1u8 {
+        v.negate();
+    }
+    let two = one.add(&one);
+    let two_minus_Z = two.sub(&Z);
+    let mut w = v.mul(&s.mul(&two_minus_Z));
+    w.ct_assign(&P::Field::one(), s.is_zero());
+    let Y = w.mul(&Z);
+    let T = w.mul(&X);
+
+    Some(DecodeState {
+        s_encoding_is_canonical: s_encoding_is_canonical,
+        s_is_negative: s_is_negative,
+        X: X, Y: Y, Z: Z, T: T,
+    })
+}
+
+/// Finish validating a `DecodeState` given `z_inv = 1/Z` (computed
+/// however the caller likes -- a plain `invert()` for a single point, or
+/// a batched inversion across many), folding the `xy >= 0` check
+/// together with the canonical/sign checks into a single decision.
+///
+/// Returns `(X, Y, Z, T)` on success, matching the fields a caller's
+/// point type needs to be constructed from.
+pub fn decode_finish<F: DecafField>(st: &DecodeState<F>, z_inv: &F) -> Option<(F, F, F, F)> {
+    // The curve/cofactor invariant: a canonical Decaf encoding always
+    // decodes to a point with xy = T/Z nonnegative.
+    let xy = st.T.mul(z_inv);
+    let xy_is_nonnegative = xy.is_nonnegative_decaf();
+
+    let is_valid = st.s_encoding_is_canonical
+        & (1u8 ^ st.s_is_negative)
+        & xy_is_nonnegative;
+
+    if is_valid == 1u8 {
+        Some((st.X, st.Y, st.Z, st.T))
+    } else {
+        None
+    }
+}
+
+/// Encode `(X, Y, Z, T)` to `s`, given `z_inv = 1/Z` (computed however
+/// the caller likes). This is the one inversion-dependent step of
+/// encoding, used to decide the pre-rotation (`COFACTOR_EIGHT == true`)
+/// or two-torsion negation (`COFACTOR_EIGHT == false`) sign choice; a
+/// batch caller amortizes it across many points via Montgomery's trick.
+pub fn encode_with_z_inv<P: DecafParams>(X_in: P::Field, Y_in: P::Field, Z: P::Field, T_in: P::Field, z_inv: &P::Field) -> P::Field {
+    let mut X = X_in;
+    let mut Y = Y_in;
+    let mut XY = T_in;
+
+    let xy = XY.mul(z_inv);
+    let is_neg_mask = 1u8 & !(Y.is_nonzero() & xy.is_nonnegative_decaf());
+
+    if P::COFACTOR_EIGHT {
+        // Cofactor 8: add Q_6 = (i,0), i.e. (X:Y:Z:T) -> (iY:iX:Z:-T).
+        let sqrt_m1 = P::sqrt_m1();
+        let iX = X.mul(&sqrt_m1);
+        let iY = Y.mul(&sqrt_m1);
+        X.ct_assign(&iY, is_neg_mask);
+        Y.ct_assign(&iX, is_neg_mask);
+        let minus_XY = XY.neg();
+        XY.ct_assign(&minus_XY, is_neg_mask);
+    } else {
+        // Cofactor 4: add the order-2 point (0,-1), i.e. negate both
+        // coordinates; x*y is invariant, so XY needs no adjustment.
+        let neg_X = X.neg();
+        let neg_Y = Y.neg();
+        X.ct_assign(&neg_X, is_neg_mask);
+        Y.ct_assign(&neg_Y, is_neg_mask);
+    }
+
+    // Compute r = 1/sqrt((a-d)(Z+Y)(Z-Y))
+    let Z_plus_Y  = Z.add(&Y);
+    let Z_minus_Y = Z.sub(&Y);
+    let a_minus_d = P::a_minus_d();
+    let t = a_minus_d.mul(&Z_plus_Y.mul(&Z_minus_Y));
+    // t should always be square for a valid point.
+    let mut r = t.invsqrt().unwrap();
+
+    // u = (a-d)r
+    let u = a_minus_d.mul(&r);
+
+    // Negate r if -2uZ is negative.
+    let uZ = u.mul(&Z);
+    let minus_r = r.neg();
+    let m2uZ = uZ.add(&uZ).neg();
+    let mask = m2uZ.is_negative_decaf();
+    r.ct_assign(&minus_r, mask);
+
+    // s = |u(r(aZX - dYT)+Y)/a|
+    let ZX = Z.mul(&X);
+    let aZX = if P::COFACTOR_EIGHT { ZX.neg() } else { ZX };
+    let dYT = P::d().mul(&Y.mul(&XY));
+    let mut s = u.mul(&r.mul(&aZX.sub(&dYT)).add(&Y));
+    s.negate();
+    s.abs_decaf()
+}
@@ -0,0 +1,324 @@
+// -*- mode: rust; -*-
+//
+// To the extent possible under law, the authors have waived all copyright and
+// related or neighboring rights to curve25519-dalek, using the Creative
+// Commons "CC0" public domain dedication.  See
+// <http://creativecommons.org/publicdomain/zero/.0/> for full details.
+
+//! Field arithmetic modulo `p = 2^448 - 2^224 - 1`, the prime underlying
+//! the Ed448-Goldilocks curve, used by `decaf448`.
+//!
+//! `FieldElement448` mirrors the public surface of `field::FieldElement`
+//! (method names like `from_bytes`/`to_bytes`, `invsqrt`,
+//! `is_negative_decaf`) so that `decaf448` can follow exactly the same
+//! decode/encode structure as `decaf`, just applied to a different field
+//! and curve.
+//!
+//! Limbs are radix `2^56` (8 limbs, 7 bytes each) so that the reduction
+//! `2^448 = 2^224 + 1 (mod p)` lines up exactly on limb boundaries:
+//! `224 = 4*56` and `448 = 8*56`.
+//!
+//! XXX this is a straightforward schoolbook implementation and has not
+//! been hardened to run in constant time to the same standard as
+//! `field::FieldElement`; that should happen before this is used for
+//! anything beyond the `decaf448` decode/encode paths it was written for.
+
+#![allow(non_snake_case)]
+
+use core::ops::{Add, Sub, Mul, Neg, Index, IndexMut};
+
+use subtle::CTAssignable;
+
+use ct_util;
+
+const MASK56: u64 = (1u64 << 56) - 1;
+
+/// `p = 2^448 - 2^224 - 1`, as radix-2^56 limbs.
+const P: [u64; 8] = [MASK56, MASK56, MASK56, MASK56, MASK56 - 1, MASK56, MASK56, MASK56];
+
+/// An element of the field `GF(2^448 - 2^224 - 1)`, as 8 radix-2^56 limbs.
+#[derive(Copy, Clone, Debug)]
+pub struct FieldElement448(pub [u64; 8]);
+
+/// Subtract `b` from `a` (both treated as 448-bit values with limbs
+/// `< 2^56`), returning `(a - b mod 2^448, borrowed)`, where `borrowed`
+/// is `true` iff `a < b`.
+fn borrow_sub(a: [u64; 8], b: [u64; 8]) -> ([u64; 8], bool) {
+    let mut out = [0u64; 8];
+    let mut borrow: i64 = 0;
+    for i in 0..8 {
+        let diff = a[i] as i64 - b[i] as i64 - borrow;
+        if diff < 0 {
+            out[i] = (diff + (1i64 << 56)) as u64;
+            borrow = 1;
+        } else {
+            out[i] = diff as u64;
+            borrow = 0;
+        }
+    }
+    (out, borrow == 1)
+}
+
+impl FieldElement448 {
+    /// The additive identity.
+    pub fn zero() -> FieldElement448 {
+        FieldElement448([0u64; 8])
+    }
+
+    /// The multiplicative identity.
+    pub fn one() -> FieldElement448 {
+        let mut limbs = [0u64; 8];
+        limbs[0] = 1;
+        FieldElement448(limbs)
+    }
+
+    /// Load a field element from its little-endian, 56-byte encoding.
+    /// The input need not be fully reduced.
+    pub fn from_bytes(bytes: &[u8; 56]) -> FieldElement448 {
+        let mut limbs = [0u64; 8];
+        for i in 0..8 {
+            let mut limb = 0u64;
+            for j in 0..7 {
+                limb |= (bytes[i * 7 + j] as u64) << (8 * j);
+            }
+            limbs[i] = limb & MASK56;
+        }
+        FieldElement448(limbs).reduce_fully()
+    }
+
+    /// Serialize to the canonical, fully-reduced 56-byte little-endian
+    /// encoding.
+    pub fn to_bytes(&self) -> [u8; 56] {
+        let reduced = self.reduce_fully();
+        let mut out = [0u8; 56];
+        for i in 0..8 {
+            let limb = reduced.0[i];
+            for j in 0..7 {
+                out[i * 7 + j] = ((limb >> (8 * j)) & 0xff) as u8;
+            }
+        }
+        out
+    }
+
+    /// Propagate carries so every limb is `< 2^56`, folding any overflow
+    /// past the top limb back in via `2^448 = 2^224 + 1 (mod p)`.
+    fn carry_propagate(&self) -> FieldElement448 {
+        let mut limbs = self.0;
+        let mut carry = 0u64;
+        for i in 0..8 {
+            let v = limbs[i] + carry;
+            limbs[i] = v & MASK56;
+            carry = v >> 56;
+        }
+        let mut passes = 0;
+        while carry != 0 && passes < 3 {
+            limbs[4] = limbs[4].wrapping_add(carry);
+            limbs[0] = limbs[0].wrapping_add(carry);
+            carry = 0;
+            for i in 0..8 {
+                let v = limbs[i] + carry;
+                limbs[i] = v & MASK56;
+                carry = v >> 56;
+            }
+            passes += 1;
+        }
+        FieldElement448(limbs)
+    }
+
+    /// Reduce to the canonical representative in `[0, p)`.
+    fn reduce_fully(&self) -> FieldElement448 {
+        let mut x = self.carry_propagate();
+        for _ in 0..2 {
+            let (t, borrowed) = borrow_sub(x.0, P);
+            if !borrowed {
+                x = FieldElement448(t);
+            }
+        }
+        x
+    }
+
+    /// Square this field element.
+    pub fn square(&self) -> FieldElement448 {
+        self * self
+    }
+
+    /// Raise this field element to the power given by `exp`, a public
+    /// (non-secret) 448-bit exponent in radix-2^56 limbs.
+    fn pow(&self, exp: [u64; 8]) -> FieldElement448 {
+        let mut result = FieldElement448::one();
+        for i in (0..8).rev() {
+            for b in (0..56).rev() {
+                result = result.square();
+                if (exp[i] >> b) & 1 == 1 {
+                    result = &result * self;
+                }
+            }
+        }
+        result
+    }
+
+    /// Compute the multiplicative inverse, or `0` if `self` is zero.
+    pub fn invert(&self) -> FieldElement448 {
+        // p - 2
+        let exp = [MASK56 - 2, MASK56, MASK56, MASK56, MASK56 - 1, MASK56, MASK56, MASK56];
+        self.pow(exp)
+    }
+
+    /// Compute `1/sqrt(self)`.
+    ///
+    /// # Return
+    ///
+    /// `Some(v)` with `v*v*self == 1` if `self` is a nonzero square,
+    /// `Some(0)` if `self` is zero, and `None` if `self` is a nonzero
+    /// non-square.
+    pub fn invsqrt(&self) -> Option<FieldElement448> {
+        let inv = self.invert();
+        // p ≡ 3 (mod 4), so sqrt(x) = x^((p+1)/4) for a square x.
+        // (p+1)/4 = 2^446 - 2^222, as radix-2^56 limbs.
+        let exp = [0u64, 0, 0, 3u64 << 54, MASK56, MASK56, MASK56, (1u64 << 54) - 1];
+        let candidate = inv.pow(exp);
+        let check = candidate.square();
+        let check_bytes = check.to_bytes();
+        let inv_bytes = inv.to_bytes();
+        let mut diff = 0u8;
+        for i in 0..56 {
+            diff |= check_bytes[i] ^ inv_bytes[i];
+        }
+        if diff == 0 {
+            Some(candidate)
+        } else {
+            None
+        }
+    }
+
+    /// `1` if `self` is the zero element, `0` otherwise.
+    pub fn is_zero(&self) -> u8 {
+        let b = self.to_bytes();
+        let mut acc = 0u8;
+        for i in 0..56 {
+            acc |= b[i];
+        }
+        ct_util::is_zero_mask(acc)
+    }
+
+    /// `1` if `self` is nonzero, `0` otherwise.
+    pub fn is_nonzero(&self) -> u8 {
+        1u8 ^ self.is_zero()
+    }
+
+    /// Decaf's sign convention: `1` if the lowest bit of the canonical
+    /// encoding is set, `0` otherwise.
+    pub fn is_negative_decaf(&self) -> u8 {
+        self.to_bytes()[0] & 1
+    }
+
+    /// The complement of `is_negative_decaf`.
+    pub fn is_nonnegative_decaf(&self) -> u8 {
+        1u8 ^ self.is_negative_decaf()
+    }
+
+    /// Negate this field element in place.
+    pub fn negate(&mut self) {
+        let x = self.reduce_fully();
+        let (t, _borrowed) = borrow_sub(P, x.0);
+        *self = FieldElement448(t).reduce_fully();
+    }
+
+    /// `-self` if `self` is negative per `is_negative_decaf`, else `self`.
+    pub fn abs_decaf(&self) -> FieldElement448 {
+        let mut t = *self;
+        if t.is_negative_decaf() == 1u8 {
+            t.negate();
+        }
+        t
+    }
+}
+
+impl CTAssignable for FieldElement448 {
+    fn conditional_assign(&mut self, other: &FieldElement448, choice: u8) {
+        let mask = (choice as u64).wrapping_neg();
+        for i in 0..8 {
+            self.0[i] = (self.0[i] & !mask) | (other.0[i] & mask);
+        }
+    }
+}
+
+impl Index<usize> for FieldElement448 {
+    type Output = u64;
+    fn index(&self, i: usize) -> &u64 {
+        &self.0[i]
+    }
+}
+
+impl IndexMut<usize> for FieldElement448 {
+    fn index_mut(&mut self, i: usize) -> &mut u64 {
+        &mut self.0[i]
+    }
+}
+
+impl<'a, 'b> Add<&'b FieldElement448> for &'a FieldElement448 {
+    type Output = FieldElement448;
+    fn add(self, other: &'b FieldElement448) -> FieldElement448 {
+        let mut limbs = [0u64; 8];
+        for i in 0..8 {
+            limbs[i] = self.0[i] + other.0[i];
+        }
+        FieldElement448(limbs).reduce_fully()
+    }
+}
+
+impl<'a, 'b> Sub<&'b FieldElement448> for &'a FieldElement448 {
+    type Output = FieldElement448;
+    fn sub(self, other: &'b FieldElement448) -> FieldElement448 {
+        let mut neg_other = *other;
+        neg_other.negate();
+        self + &neg_other
+    }
+}
+
+impl<'a> Neg for &'a FieldElement448 {
+    type Output = FieldElement448;
+    fn neg(self) -> FieldElement448 {
+        let mut t = *self;
+        t.negate();
+        t
+    }
+}
+
+impl<'a, 'b> Mul<&'b FieldElement448> for &'a FieldElement448 {
+    type Output = FieldElement448;
+    fn mul(self, other: &'b FieldElement448) -> FieldElement448 {
+        let mut acc = [0u128; 15];
+        for i in 0..8 {
+            for j in 0..8 {
+                acc[i + j] += (self.0[i] as u128) * (other.0[j] as u128);
+            }
+        }
+
+        // Fold coefficients at index >= 8 down using
+        // 2^(56k) = 2^(56(k-8)) * 2^448 ≡ 2^(56(k-4)) + 2^(56(k-8))  (mod p).
+        for k in (8..15).rev() {
+            let c = acc[k];
+            acc[k] = 0;
+            acc[k - 4] += c;
+            acc[k - 8] += c;
+        }
+
+        let mut limbs = [0u64; 8];
+        let mut carry: u128 = 0;
+        for i in 0..8 {
+            let v = acc[i] + carry;
+            limbs[i] = (v & (MASK56 as u128)) as u64;
+            carry = v >> 56;
+        }
+
+        let mut result = FieldElement448(limbs);
+        if carry != 0 {
+            let c = carry as u64;
+            result.0[4] = result.0[4].wrapping_add(c);
+            result.0[0] = result.0[0].wrapping_add(c);
+            result = result.carry_propagate();
+        }
+        result.reduce_fully()
+    }
+}